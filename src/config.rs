@@ -0,0 +1,93 @@
+use khronos_egl as egl;
+
+/// Which EGL client API to bind before creating the context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlApi {
+    OpenGlEs,
+    OpenGl,
+}
+
+impl GlApi {
+    pub(crate) fn egl_api_enum(self) -> egl::Enum {
+        match self {
+            Self::OpenGlEs => egl::OPENGL_ES_API,
+            Self::OpenGl => egl::OPENGL_API,
+        }
+    }
+
+    pub(crate) fn renderable_bit(self) -> egl::Int {
+        match self {
+            Self::OpenGlEs => egl::OPENGL_ES2_BIT,
+            Self::OpenGl => egl::OPENGL_BIT,
+        }
+    }
+}
+
+/// Describes the GL API, context version, and EGL framebuffer
+/// configuration `WaylandEGLState::init_egl` should request. Replaces the
+/// previously hard-coded GLES2-over-8888 config so callers can run the
+/// desktop-GL shaders or ask for a depth/stencil buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct EglConfig {
+    pub api: GlApi,
+    pub major: u8,
+    pub minor: u8,
+    pub red_bits: u8,
+    pub green_bits: u8,
+    pub blue_bits: u8,
+    pub alpha_bits: u8,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+}
+
+impl Default for EglConfig {
+    /// Matches the GLES2 + 8888, no depth/stencil config this crate shipped
+    /// before `EglConfig` existed.
+    fn default() -> Self {
+        Self {
+            api: GlApi::OpenGlEs,
+            major: 2,
+            minor: 0,
+            red_bits: 8,
+            green_bits: 8,
+            blue_bits: 8,
+            alpha_bits: 8,
+            depth_bits: 0,
+            stencil_bits: 0,
+        }
+    }
+}
+
+impl EglConfig {
+    pub(crate) fn surface_attributes(&self) -> [egl::Int; 17] {
+        [
+            egl::SURFACE_TYPE,
+            egl::WINDOW_BIT,
+            egl::RENDERABLE_TYPE,
+            self.api.renderable_bit(),
+            egl::RED_SIZE,
+            self.red_bits as egl::Int,
+            egl::GREEN_SIZE,
+            self.green_bits as egl::Int,
+            egl::BLUE_SIZE,
+            self.blue_bits as egl::Int,
+            egl::ALPHA_SIZE,
+            self.alpha_bits as egl::Int,
+            egl::DEPTH_SIZE,
+            self.depth_bits as egl::Int,
+            egl::STENCIL_SIZE,
+            self.stencil_bits as egl::Int,
+            egl::NONE,
+        ]
+    }
+
+    pub(crate) fn context_attributes(&self) -> [egl::Int; 5] {
+        [
+            egl::CONTEXT_MAJOR_VERSION_KHR,
+            self.major as egl::Int,
+            egl::CONTEXT_MINOR_VERSION_KHR,
+            self.minor as egl::Int,
+            egl::NONE,
+        ]
+    }
+}
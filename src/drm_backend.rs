@@ -0,0 +1,246 @@
+use crate::config::EglConfig;
+use crate::error::{Result, WaylandEGLStateError};
+use crate::gl_renderer::GlRenderer;
+use crate::state::{init_egl_context, EglInstance};
+use crate::utils::load_gl_functions;
+
+use drm::control::{connector, crtc, framebuffer, Device as ControlDevice, PageFlipFlags};
+use drm::Device as BasicDevice;
+use gbm::{BufferObjectFlags, Device as GbmDevice, Format as GbmFormat, Surface as GbmSurface};
+use khronos_egl as egl;
+use std::{
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+};
+
+/// The open DRM device node. `drm::Device` is just a trait over something
+/// that owns a file descriptor, and `gbm::Device` wraps such a value, so
+/// this is the only type that actually holds the `File`.
+#[derive(Debug)]
+struct Card(File);
+
+impl AsRawFd for Card {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl BasicDevice for Card {}
+impl ControlDevice for Card {}
+
+/// Headless rendering backend that drives EGL over a DRM/GBM surface
+/// instead of a `wl_surface`, for running this crate's GLES2 triangle on a
+/// bare TTY or an embedded target without a Wayland compositor.
+pub struct DrmEGLState {
+    gbm: GbmDevice<Card>,
+    gbm_surface: GbmSurface<()>,
+    crtc: crtc::Handle,
+    front_buffer: Option<gbm::BufferObject<()>>,
+    /// The framebuffer the CRTC is currently scanning out. Destroyed only
+    /// once `present()` has flipped away from it, never the one just
+    /// flipped to.
+    current_fb: Option<framebuffer::Handle>,
+
+    egl: EglInstance,
+    egl_display: egl::Display,
+    egl_surface: egl::Surface,
+    egl_context: egl::Context,
+
+    renderer: GlRenderer,
+
+    pub width: i32,
+    pub height: i32,
+}
+
+impl DrmEGLState {
+    /// Opens `device_path` (e.g. `/dev/dri/card0`), picks the first
+    /// connected connector and its preferred mode, and sets up a GBM
+    /// surface + EGL context for it using the same `EglConfig` the Wayland
+    /// backend accepts.
+    #[tracing::instrument(skip(egl_config))]
+    pub fn new(
+        device_path: &Path,
+        egl_config: &EglConfig,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let card = Card(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(device_path)
+                .map_err(|err| WaylandEGLStateError::DrmDeviceOpenFailed(err.to_string()))?,
+        );
+
+        let resources = card
+            .resource_handles()
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        let connector_info = resources
+            .connectors()
+            .iter()
+            .filter_map(|&handle| card.get_connector(handle, false).ok())
+            .find(|connector| connector.state() == connector::State::Connected)
+            .ok_or_else(|| {
+                WaylandEGLStateError::DrmModeSetFailed("no connected connector found".into())
+            })?;
+
+        let mode = *connector_info.modes().first().ok_or_else(|| {
+            WaylandEGLStateError::DrmModeSetFailed("connector has no usable mode".into())
+        })?;
+
+        let crtc = resources.crtcs().first().copied().ok_or_else(|| {
+            WaylandEGLStateError::DrmModeSetFailed("no crtc available".into())
+        })?;
+
+        let (width, height) = mode.size();
+        let (width, height) = (width as i32, height as i32);
+
+        let gbm = GbmDevice::new(card)
+            .map_err(|err| WaylandEGLStateError::DrmDeviceOpenFailed(err.to_string()))?;
+
+        let gbm_surface = gbm
+            .create_surface::<()>(
+                width as u32,
+                height as u32,
+                GbmFormat::Xrgb8888,
+                BufferObjectFlags::SCANOUT | BufferObjectFlags::RENDERING,
+            )
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        let egl = EglInstance::new_static();
+        let egl_display = unsafe { egl.get_display(gbm.as_raw() as *mut c_void) }
+            .ok_or_else(|| WaylandEGLStateError::DrmModeSetFailed("eglGetDisplay failed".into()))?;
+
+        let (egl_surface, egl_context) = init_egl_context(
+            &egl,
+            egl_display,
+            gbm_surface.as_raw() as egl::NativeWindowType,
+            egl_config,
+        )?;
+
+        load_gl_functions();
+
+        let mut renderer = GlRenderer::default();
+        renderer.init()?;
+
+        // Render and swap once so there is a buffer to hand to the initial
+        // modeset: legacy KMS requires a CRTC to already have a
+        // connector/mode/framebuffer bound (via set_crtc) before a
+        // page_flip against it is valid.
+        renderer.draw(width, height, 0);
+        egl.swap_buffers(egl_display, egl_surface)?;
+
+        let first_buffer = gbm_surface
+            .lock_front_buffer()
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+        let first_fb = gbm
+            .add_framebuffer(&first_buffer, 24, 32)
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        gbm.set_crtc(
+            crtc,
+            Some(first_fb),
+            (0, 0),
+            &[connector_info.handle()],
+            Some(mode),
+        )
+        .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        Ok(Self {
+            gbm,
+            gbm_surface,
+            crtc,
+            front_buffer: Some(first_buffer),
+            current_fb: Some(first_fb),
+            egl,
+            egl_display,
+            egl_surface,
+            egl_context,
+            renderer,
+            width,
+            height,
+        })
+    }
+
+    pub fn draw(&self, frame_time_ms: u32) {
+        self.renderer.draw(self.width, self.height, frame_time_ms);
+    }
+
+    /// Swaps the EGL surface, locks the next GBM front buffer, wraps it in
+    /// a DRM framebuffer, and page-flips `self.crtc` onto it. The buffer
+    /// that was on screen before this flip is released back to GBM once
+    /// the kernel reports the flip event.
+    pub fn present(&mut self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        self.egl.swap_buffers(self.egl_display, self.egl_surface)?;
+
+        let next_buffer = self
+            .gbm_surface
+            .lock_front_buffer()
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        let fb = self
+            .gbm
+            .add_framebuffer(&next_buffer, 24, 32)
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        self.gbm
+            .page_flip(self.crtc, fb, PageFlipFlags::EVENT, None)
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+
+        for event in self
+            .gbm
+            .receive_events()
+            .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?
+        {
+            if let drm::control::Event::PageFlip(_) = event {
+                // `fb` is now the active scanout buffer; only the
+                // previously-active one (if any) is safe to tear down.
+                if let Some(previous_fb) = self.current_fb.replace(fb) {
+                    self.gbm
+                        .destroy_framebuffer(previous_fb)
+                        .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+                }
+                if let Some(previous_buffer) = self.front_buffer.replace(next_buffer) {
+                    self.gbm_surface.release_buffer(previous_buffer);
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn deinit(self: &Self) -> Result<(), Box<dyn std::error::Error>> {
+        self.renderer.deinit();
+
+        if let Some(fb) = self.current_fb {
+            self.gbm
+                .destroy_framebuffer(fb)
+                .map_err(|err| WaylandEGLStateError::DrmModeSetFailed(err.to_string()))?;
+        }
+
+        self.egl.destroy_surface(self.egl_display, self.egl_surface)?;
+        self.egl.destroy_context(self.egl_display, self.egl_context)?;
+
+        Ok(())
+    }
+}
+
+impl framebuffer::Buffer for gbm::BufferObject<()> {
+    fn size(&self) -> (u32, u32) {
+        (self.width().unwrap_or(0), self.height().unwrap_or(0))
+    }
+
+    fn format(&self) -> drm::buffer::DrmFourcc {
+        drm::buffer::DrmFourcc::Xrgb8888
+    }
+
+    fn pitch(&self) -> u32 {
+        self.stride().unwrap_or(0)
+    }
+
+    fn handle(&self) -> drm::buffer::Handle {
+        self.handle().into()
+    }
+}
@@ -0,0 +1,32 @@
+use khronos_egl as egl;
+use std::result;
+use thiserror::Error;
+
+pub type Result<T, E = WaylandEGLStateError> = result::Result<T, E>;
+
+#[derive(Error, Debug)]
+pub enum WaylandEGLStateError {
+    #[error("xdg_wm_base global missing")]
+    XdgWmBaseMissing,
+
+    #[error("wl_compositor global missing")]
+    WlCompositorMissing,
+
+    #[error("Shader compilation failed:\n{log}")]
+    GLShaderCompileFailed { log: String },
+
+    #[error("Failed to create gl program")]
+    GLCreateProgramFailed,
+
+    #[error("Failed to link gl program:\n{log}")]
+    GLLinkProgramFailed { log: String },
+
+    #[error("Failed to load libEGL dynamically: {0}")]
+    EglDynamicLoadFailed(#[from] egl::Error),
+
+    #[error("Failed to open DRM device: {0}")]
+    DrmDeviceOpenFailed(String),
+
+    #[error("Failed to set up the DRM mode: {0}")]
+    DrmModeSetFailed(String),
+}
@@ -0,0 +1,83 @@
+use crate::error::{Result, WaylandEGLStateError};
+use crate::utils::{load_shader, program_info_log};
+
+use gl::types::GLuint;
+use std::mem::transmute;
+
+/// The GLES2/GL triangle program, factored out of `WaylandEGLState` so it
+/// can be driven identically from a `wl_surface`-backed EGL surface or a
+/// GBM-backed one once either has made its EGL context current.
+#[derive(Debug, Default)]
+pub struct GlRenderer {
+    program: GLuint,
+}
+
+impl GlRenderer {
+    pub fn init(&mut self) -> Result<()> {
+        let vert_shader = load_shader(
+            gl::VERTEX_SHADER,
+            include_str!("./shaders/shader.vert").into(),
+        )?;
+
+        let frag_shader = load_shader(
+            gl::FRAGMENT_SHADER,
+            include_str!("./shaders/shader.frag").into(),
+        )?;
+
+        unsafe {
+            self.program = gl::CreateProgram();
+        }
+
+        if self.program == 0 {
+            tracing::event!(tracing::Level::ERROR, "glCreateProgramFailed!");
+            return Err(WaylandEGLStateError::GLCreateProgramFailed);
+        }
+
+        unsafe {
+            gl::AttachShader(self.program, vert_shader);
+            gl::AttachShader(self.program, frag_shader);
+
+            gl::LinkProgram(self.program);
+        }
+
+        let mut linked: gl::types::GLint = 1;
+        unsafe { gl::GetProgramiv(self.program, gl::LINK_STATUS, &mut linked as *mut i32) }
+
+        if linked > 0 {
+            tracing::event!(tracing::Level::INFO, "Successfully linked the program!");
+        } else {
+            let log = unsafe { program_info_log(self.program) };
+            tracing::event!(tracing::Level::ERROR, "glLinkProgram failed:\n{}", log);
+            return Err(WaylandEGLStateError::GLLinkProgramFailed { log });
+        }
+
+        Ok(())
+    }
+
+    /// `frame_time_ms` is the compositor's `wl_callback::Done` timestamp
+    /// for the frame being drawn on the Wayland path (the DRM backend has
+    /// no compositor and passes 0); unused by the static triangle today, but
+    /// threaded through so animated content has elapsed time to advance by.
+    pub fn draw(&self, width: i32, height: i32, _frame_time_ms: u32) {
+        let ptr: [gl::types::GLfloat; 9] = [0.0, 1.0, 0.0, -1.0, -1.0, 0.0, 1.0, -1.0, 0.0];
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+
+            gl::ClearColor(1.0, 1.0, 1.0, 0.0);
+            gl::Clear(gl::CLEAR_BUFFER);
+
+            gl::UseProgram(self.program);
+
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, transmute(&ptr[0]));
+
+            gl::EnableVertexAttribArray(0);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+
+    pub fn deinit(&self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
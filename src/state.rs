@@ -1,9 +1,9 @@
+use crate::config::EglConfig;
 use crate::error::{Result, WaylandEGLStateError};
-use crate::utils::load_shader;
+use crate::gl_renderer::GlRenderer;
 
-use gl::types::GLuint;
 use khronos_egl as egl;
-use std::{ffi::c_void, mem::transmute, rc::Rc};
+use std::{ffi::c_void, path::Path, rc::Rc};
 use wayland_client::{
     protocol::{wl_compositor, wl_display::WlDisplay, wl_surface::WlSurface},
     ConnectError, Connection, Proxy,
@@ -11,6 +11,168 @@ use wayland_client::{
 use wayland_egl::WlEglSurface;
 use wayland_protocols::xdg::shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
 
+/// Wraps either a build-time-linked (`egl::Static`) or a runtime-loaded
+/// (`egl::Dynamic`) EGL entry point behind a single call surface, so
+/// callers don't need to be generic over the loading strategy.
+#[derive(Debug)]
+pub enum EglInstance {
+    Static(egl::Instance<egl::Static>),
+    Dynamic(egl::DynamicInstance<egl::EGL1_4>),
+}
+
+impl EglInstance {
+    pub(crate) fn new_static() -> Self {
+        Self::Static(egl::Instance::new(egl::Static))
+    }
+
+    /// Opens `libEGL.so.1` (or the library at `path`, if given) with
+    /// `libloading` and builds an EGL instance backed by it, for systems
+    /// where the EGL vendor library is only resolvable at runtime.
+    pub(crate) fn new_dynamic(path: Option<&Path>) -> Result<Self> {
+        let instance = unsafe {
+            match path {
+                Some(path) => egl::DynamicInstance::<egl::EGL1_4>::load_required_from(path),
+                None => egl::DynamicInstance::<egl::EGL1_4>::load_required(),
+            }
+        }?;
+
+        Ok(Self::Dynamic(instance))
+    }
+
+    pub(crate) fn bind_api(&self, api: egl::Enum) -> egl::Result<()> {
+        match self {
+            Self::Static(egl) => egl.bind_api(api),
+            Self::Dynamic(egl) => egl.bind_api(api),
+        }
+    }
+
+    pub(crate) fn get_display(&self, display_id: egl::NativeDisplayType) -> Option<egl::Display> {
+        match self {
+            Self::Static(egl) => egl.get_display(display_id),
+            Self::Dynamic(egl) => egl.get_display(display_id),
+        }
+    }
+
+    pub(crate) fn initialize(&self, display: egl::Display) -> egl::Result<(egl::Int, egl::Int)> {
+        match self {
+            Self::Static(egl) => egl.initialize(display),
+            Self::Dynamic(egl) => egl.initialize(display),
+        }
+    }
+
+    pub(crate) fn choose_first_config(
+        &self,
+        display: egl::Display,
+        attributes: &[egl::Int],
+    ) -> egl::Result<Option<egl::Config>> {
+        match self {
+            Self::Static(egl) => egl.choose_first_config(display, attributes),
+            Self::Dynamic(egl) => egl.choose_first_config(display, attributes),
+        }
+    }
+
+    pub(crate) unsafe fn create_window_surface(
+        &self,
+        display: egl::Display,
+        config: egl::Config,
+        native_window: egl::NativeWindowType,
+        attrib_list: Option<&[egl::Int]>,
+    ) -> egl::Result<egl::Surface> {
+        match self {
+            Self::Static(egl) => {
+                egl.create_window_surface(display, config, native_window, attrib_list)
+            }
+            Self::Dynamic(egl) => {
+                egl.create_window_surface(display, config, native_window, attrib_list)
+            }
+        }
+    }
+
+    pub(crate) fn create_context(
+        &self,
+        display: egl::Display,
+        config: egl::Config,
+        share_context: Option<egl::Context>,
+        attributes: &[egl::Int],
+    ) -> egl::Result<egl::Context> {
+        match self {
+            Self::Static(egl) => egl.create_context(display, config, share_context, attributes),
+            Self::Dynamic(egl) => egl.create_context(display, config, share_context, attributes),
+        }
+    }
+
+    pub(crate) fn make_current(
+        &self,
+        display: egl::Display,
+        draw_surface: Option<egl::Surface>,
+        read_surface: Option<egl::Surface>,
+        context: Option<egl::Context>,
+    ) -> egl::Result<()> {
+        match self {
+            Self::Static(egl) => egl.make_current(display, draw_surface, read_surface, context),
+            Self::Dynamic(egl) => egl.make_current(display, draw_surface, read_surface, context),
+        }
+    }
+
+    pub fn swap_buffers(&self, display: egl::Display, surface: egl::Surface) -> egl::Result<()> {
+        match self {
+            Self::Static(egl) => egl.swap_buffers(display, surface),
+            Self::Dynamic(egl) => egl.swap_buffers(display, surface),
+        }
+    }
+
+    pub(crate) fn destroy_surface(
+        &self,
+        display: egl::Display,
+        surface: egl::Surface,
+    ) -> egl::Result<()> {
+        match self {
+            Self::Static(egl) => egl.destroy_surface(display, surface),
+            Self::Dynamic(egl) => egl.destroy_surface(display, surface),
+        }
+    }
+
+    pub(crate) fn destroy_context(
+        &self,
+        display: egl::Display,
+        context: egl::Context,
+    ) -> egl::Result<()> {
+        match self {
+            Self::Static(egl) => egl.destroy_context(display, context),
+            Self::Dynamic(egl) => egl.destroy_context(display, context),
+        }
+    }
+}
+
+/// Binds `egl_config.api`, picks the first matching EGL config, and creates
+/// a window surface + context over `native_window` and makes them current.
+/// Shared by the Wayland (`WaylandEGLState::init_egl`) and DRM/GBM
+/// (`crate::drm_backend::DrmEGLState::new`) backends so the two only differ
+/// in how they obtain `egl_display`/`native_window`, not in how the EGL
+/// context itself is set up.
+pub(crate) fn init_egl_context(
+    egl: &EglInstance,
+    egl_display: egl::Display,
+    native_window: egl::NativeWindowType,
+    egl_config: &EglConfig,
+) -> Result<(egl::Surface, egl::Context), Box<dyn std::error::Error>> {
+    egl.initialize(egl_display)?;
+    egl.bind_api(egl_config.api.egl_api_enum())?;
+
+    let config = egl
+        .choose_first_config(egl_display, &egl_config.surface_attributes())?
+        .expect("unable to find an appropriate EGL configuration");
+
+    let surface = unsafe { egl.create_window_surface(egl_display, config, native_window, None)? };
+
+    let context =
+        egl.create_context(egl_display, config, None, &egl_config.context_attributes())?;
+
+    egl.make_current(egl_display, Some(surface), Some(surface), Some(context))?;
+
+    Ok((surface, context))
+}
+
 #[derive(Debug)]
 pub struct WaylandEGLState {
     pub width: i32,
@@ -18,17 +180,26 @@ pub struct WaylandEGLState {
     pub running: bool,
     pub title: String,
 
+    /// Set by the `wl_callback::Done` handler once the compositor has
+    /// released the last frame; only draw and swap when this is set so the
+    /// render loop sleeps instead of spinning while occluded/throttled.
+    pub redraw_needed: bool,
+    /// `callback_data` (frame time in milliseconds) from the last
+    /// `wl_callback::Done` event, so `draw` can advance animated content by
+    /// elapsed time instead of by wall-clock polling.
+    pub last_frame_time_ms: u32,
+
     pub wl_connection: Connection,
     pub wl_display: WlDisplay,
     pub wl_surface: Option<WlSurface>,
 
-    pub egl: egl::Instance<egl::Static>,
+    pub egl: EglInstance,
     pub egl_window: Option<Rc<WlEglSurface>>,
     pub egl_display: Option<egl::Display>,
     pub egl_surface: Option<egl::Surface>,
     pub egl_context: Option<egl::Context>,
 
-    pub gl_program: GLuint,
+    pub renderer: GlRenderer,
 
     pub xdg_wm_base: Option<xdg_wm_base::XdgWmBase>,
     pub xdg_surface: Option<xdg_surface::XdgSurface>,
@@ -39,6 +210,20 @@ pub struct WaylandEGLState {
 impl WaylandEGLState {
     #[tracing::instrument]
     pub fn new() -> Result<Self, ConnectError> {
+        Self::new_with_egl(EglInstance::new_static())
+    }
+
+    /// Like [`WaylandEGLState::new`], but resolves libEGL at runtime via
+    /// `libloading` instead of linking it at build time. `path` overrides
+    /// the default search for `libEGL.so.1`.
+    #[tracing::instrument]
+    pub fn new_dynamic(
+        path: Option<&Path>,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new_with_egl(EglInstance::new_dynamic(path)?)?)
+    }
+
+    fn new_with_egl(egl: EglInstance) -> Result<Self, ConnectError> {
         let server_connection = Connection::connect_to_env()?;
         Ok(Self {
             width: 320,
@@ -46,17 +231,20 @@ impl WaylandEGLState {
             running: true,
             title: "Nya".into(),
 
+            redraw_needed: true,
+            last_frame_time_ms: 0,
+
             wl_connection: server_connection.clone(),
             wl_display: server_connection.display(),
             wl_surface: None,
 
-            egl: egl::Instance::new(egl::Static),
+            egl,
             egl_window: None,
             egl_display: None,
             egl_surface: None,
             egl_context: None,
 
-            gl_program: 0,
+            renderer: GlRenderer::default(),
 
             xdg_wm_base: None,
             xdg_surface: None,
@@ -66,9 +254,7 @@ impl WaylandEGLState {
     }
 
     pub fn deinit(self: &Self) -> Result<(), Box<dyn std::error::Error>> {
-        unsafe {
-            gl::DeleteProgram(self.gl_program);
-        }
+        self.renderer.deinit();
 
         self.egl
             .destroy_surface(self.egl_display.unwrap(), self.egl_surface.unwrap())?;
@@ -81,11 +267,10 @@ impl WaylandEGLState {
         Ok(())
     }
 
-    pub fn init_egl(self: &mut Self) -> Result<(), Box<dyn std::error::Error>> {
-        // Init gl
-        gl_loader::init_gl();
-        gl::load_with(|s| gl_loader::get_proc_address(s) as *const _);
-
+    pub fn init_egl(
+        self: &mut Self,
+        egl_config: &EglConfig,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         self.egl_window = Some(Rc::new(WlEglSurface::new(
             self.wl_surface.clone().unwrap().id(),
             self.width,
@@ -100,112 +285,24 @@ impl WaylandEGLState {
             .unwrap(),
         );
 
-        self.egl.initialize(self.egl_display.unwrap())?;
-
-        let attributes = [
-            egl::SURFACE_TYPE,
-            egl::WINDOW_BIT,
-            egl::RENDERABLE_TYPE,
-            egl::OPENGL_ES2_BIT,
-            egl::RED_SIZE,
-            8,
-            egl::GREEN_SIZE,
-            8,
-            egl::BLUE_SIZE,
-            8,
-            egl::ALPHA_SIZE,
-            8,
-            egl::NONE,
-        ];
-
-        let config = self
-            .egl
-            .choose_first_config(self.egl_display.unwrap(), &attributes)?
-            .expect("unable to find an appropriate EGL configuration");
-
-        self.egl_surface = Some(unsafe {
-            self.egl.create_window_surface(
-                self.egl_display.unwrap(),
-                config,
-                self.egl_window.clone().unwrap().ptr() as egl::NativeWindowType,
-                None,
-            )?
-        });
-
-        let context_attributes = [egl::CONTEXT_CLIENT_VERSION, 2, egl::NONE, egl::NONE];
-        self.egl_context = Some(self.egl.create_context(
-            self.egl_display.unwrap(),
-            config,
-            None,
-            &context_attributes,
-        )?);
-
-        self.egl.make_current(
+        let (surface, context) = init_egl_context(
+            &self.egl,
             self.egl_display.unwrap(),
-            self.egl_surface,
-            self.egl_surface,
-            self.egl_context,
+            self.egl_window.clone().unwrap().ptr() as egl::NativeWindowType,
+            egl_config,
         )?;
+        self.egl_surface = Some(surface);
+        self.egl_context = Some(context);
 
-        self.init_program()?;
-
-        Ok(())
-    }
-
-    fn init_program(self: &mut Self) -> Result<()> {
-        let vert_shader = load_shader(
-            gl::VERTEX_SHADER,
-            include_str!("./shaders/shader.vert").into(),
-        )
-        .unwrap();
-
-        let frag_shader = load_shader(
-            gl::FRAGMENT_SHADER,
-            include_str!("./shaders/shader.frag").into(),
-        )
-        .unwrap();
-
-        unsafe {
-            self.gl_program = gl::CreateProgram();
-        }
-
-        if self.gl_program == 0 {
-            tracing::event!(tracing::Level::ERROR, "glCreateProgramFailed!");
-            return Err(WaylandEGLStateError::GLCreateProgramFailed);
-        }
-
-        unsafe {
-            gl::AttachShader(self.gl_program, vert_shader);
-            gl::AttachShader(self.gl_program, frag_shader);
-
-            gl::LinkProgram(self.gl_program);
-        }
-
-        let mut linked: gl::types::GLint = 1;
-        unsafe { gl::GetProgramiv(self.gl_program, gl::LINK_STATUS, &mut linked as *mut i32) }
-
-        if linked > 0 {
-            tracing::event!(tracing::Level::INFO, "Successfully linked the program!");
-        } else {
-            return Err(WaylandEGLStateError::GLLinkProgramFailed);
-        }
+        crate::utils::load_gl_functions();
+        self.renderer.init()?;
 
         Ok(())
     }
 
     pub fn draw(self: &Self) {
-        let ptr: [gl::types::GLfloat; 9] = [0.0, 1.0, 0.0, -1.0, -1.0, 0.0, 1.0, -1.0, 0.0];
-        unsafe {
-            gl::ClearColor(1.0, 1.0, 1.0, 0.0);
-            gl::Clear(gl::CLEAR_BUFFER);
-
-            gl::UseProgram(self.gl_program);
-
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, transmute(&ptr[0]));
-
-            gl::EnableVertexAttribArray(0);
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
-        }
+        self.renderer
+            .draw(self.width, self.height, self.last_frame_time_ms);
     }
 
     pub fn validate_globals(self: &Self) -> Result<()> {
@@ -218,3 +315,39 @@ impl WaylandEGLState {
         Ok(())
     }
 }
+
+/// Lets external GPU stacks (glutin, wgpu, skia, ...) drive this window
+/// through the common `rwh_06`-based windowing ecosystem, instead of going
+/// through `draw`/`init_egl`.
+#[cfg(feature = "raw-window-handle")]
+mod raw_handle {
+    use super::WaylandEGLState;
+
+    use raw_window_handle::{
+        DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+        RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, WindowHandle,
+    };
+    use std::ptr::NonNull;
+    use wayland_client::Proxy;
+
+    impl HasWindowHandle for WaylandEGLState {
+        fn window_handle(&self) -> std::result::Result<WindowHandle<'_>, HandleError> {
+            let wl_surface = self.wl_surface.as_ref().ok_or(HandleError::Unavailable)?;
+            let ptr =
+                NonNull::new(wl_surface.id().as_ptr() as *mut _).ok_or(HandleError::Unavailable)?;
+
+            let handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(ptr));
+            Ok(unsafe { WindowHandle::borrow_raw(handle) })
+        }
+    }
+
+    impl HasDisplayHandle for WaylandEGLState {
+        fn display_handle(&self) -> std::result::Result<DisplayHandle<'_>, HandleError> {
+            let ptr = NonNull::new(self.wl_display.id().as_ptr() as *mut _)
+                .ok_or(HandleError::Unavailable)?;
+
+            let handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(ptr));
+            Ok(unsafe { DisplayHandle::borrow_raw(handle) })
+        }
+    }
+}
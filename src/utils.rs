@@ -0,0 +1,82 @@
+use crate::error::{Result, WaylandEGLStateError};
+
+use gl::types::{GLenum, GLint, GLuint};
+use std::{ffi::CString, os::raw::c_char, ptr};
+
+/// Loads `libGL`/`libGLESv2` and resolves every `gl::*` function pointer
+/// against the current EGL context. Must run after `eglMakeCurrent` and
+/// before any `gl::*` call — both the Wayland and DRM backends call this
+/// right before handing off to `GlRenderer::init`.
+pub fn load_gl_functions() {
+    gl_loader::init_gl();
+    gl::load_with(|s| gl_loader::get_proc_address(s) as *const _);
+}
+
+pub fn load_shader(shader_type: GLenum, src: String) -> Result<GLuint> {
+    unsafe {
+        let shader: GLuint = gl::CreateShader(shader_type);
+        if shader == 0 {
+            return Err(WaylandEGLStateError::GLShaderCompileFailed { log: String::new() });
+        }
+
+        let src_c_str = CString::new(src.as_bytes()).unwrap();
+        gl::ShaderSource(shader, 1, &src_c_str.as_ptr(), ptr::null());
+
+        gl::CompileShader(shader);
+
+        let mut status: GLint = 1;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status as *mut i32);
+
+        if status > 0 {
+            tracing::event!(tracing::Level::INFO, "Shader compile successfull!",);
+        } else {
+            let log = shader_info_log(shader);
+            tracing::event!(tracing::Level::ERROR, "glCompileShader failed:\n{}", log);
+            return Err(WaylandEGLStateError::GLShaderCompileFailed { log });
+        }
+
+        Ok(shader)
+    }
+}
+
+/// Reads back the driver's `glGetShaderInfoLog` for a shader that failed to
+/// compile, sizing the buffer from `GL_INFO_LOG_LENGTH` first.
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut log_length: GLint = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_length as *mut i32);
+
+    read_info_log(log_length, |buf_size, written, buf| {
+        gl::GetShaderInfoLog(shader, buf_size, written, buf)
+    })
+}
+
+/// Reads back the driver's `glGetProgramInfoLog` for a program that failed
+/// to link, sizing the buffer from `GL_INFO_LOG_LENGTH` first.
+pub unsafe fn program_info_log(program: GLuint) -> String {
+    let mut log_length: GLint = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut log_length as *mut i32);
+
+    read_info_log(log_length, |buf_size, written, buf| {
+        gl::GetProgramInfoLog(program, buf_size, written, buf)
+    })
+}
+
+unsafe fn read_info_log(
+    log_length: GLint,
+    get_log: impl FnOnce(gl::types::GLsizei, *mut gl::types::GLsizei, *mut c_char),
+) -> String {
+    if log_length <= 0 {
+        return String::new();
+    }
+
+    let mut buffer = vec![0u8; log_length as usize];
+    let mut written: gl::types::GLsizei = 0;
+    get_log(
+        log_length,
+        &mut written as *mut gl::types::GLsizei,
+        buffer.as_mut_ptr() as *mut c_char,
+    );
+    buffer.truncate(written.max(0) as usize);
+
+    String::from_utf8_lossy(&buffer).into_owned()
+}